@@ -0,0 +1,141 @@
+#![no_std]
+
+//! Wire protocol shared between the host tool and the board's control channel.
+//!
+//! A frame is a [`HostCommand`] or [`DeviceResponse`] value, `postcard`-serialized and
+//! then COBS-encoded so the payload contains no `0x00` byte, with a single `0x00`
+//! terminating the frame. Because COBS guarantees the only zero byte in the stream is
+//! the delimiter, a reader that gets out of sync (a dropped byte, a garbled length)
+//! can always resynchronize by skipping ahead to the next `0x00` rather than having to
+//! re-derive a byte offset.
+
+use heapless::{String, Vec};
+use serde::{Deserialize, Serialize};
+
+/// Largest payload carried by a single command or response.
+pub const MAX_PAYLOAD_LEN: usize = 252;
+
+/// Largest frame, postcard-encoded and then COBS-encoded with its terminator, that this
+/// protocol will produce or accept.
+///
+/// The worst case is `DfuCommand::Chunk { offset: u32, data: Vec<u8, MAX_PAYLOAD_LEN> }`:
+/// `id` (1 byte) + `bus` (1) + the `CommandKind` tag (1) + the
+/// `DfuCommand` tag (1) + `offset` (5, varint) + the `Vec`'s length varint (2) +
+/// `MAX_PAYLOAD_LEN` data bytes, plus COBS's one overhead byte per 254 payload bytes
+/// and its `0x00` terminator. 32 bytes of headroom over `MAX_PAYLOAD_LEN` covers all of
+/// that with room to spare.
+pub const MAX_FRAME_LEN: usize = MAX_PAYLOAD_LEN + 32;
+
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub struct HostCommand {
+    pub id: i8,
+    pub bus: u8,
+    pub inner: CommandKind,
+}
+
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum CommandKind {
+    I2c(I2cCommand),
+    Gpio(GpioCommand),
+    Led(LedCommand),
+    Dfu(DfuCommand),
+    Clock(ClockCommand),
+    System(SystemCommand),
+}
+
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum I2cCommand {
+    Write { addr: u8, data: Vec<u8, MAX_PAYLOAD_LEN> },
+    Read { addr: u8, len: u8 },
+    WriteRead { addr: u8, data: Vec<u8, MAX_PAYLOAD_LEN>, read_len: u8 },
+}
+
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum GpioCommand {
+    SetAsicPwrEn(bool),
+    SetAsicResetn(bool),
+    ReadPins,
+}
+
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum LedCommand {
+    Set { r: u8, g: u8, b: u8 },
+    Off,
+}
+
+/// A firmware update transferred in chunks through the control channel and committed
+/// once the host confirms its CRC matches what was written to the DFU partition.
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum DfuCommand {
+    /// Start a transfer of `total_len` bytes, expected to CRC32 to `crc32` once written.
+    Begin { total_len: u32, crc32: u32 },
+    /// Write `data` at `offset` bytes into the DFU partition.
+    Chunk { offset: u32, data: Vec<u8, MAX_PAYLOAD_LEN> },
+    /// Verify the accumulated CRC, mark the DFU partition updated, and reset into it.
+    Commit,
+    /// Report the bootloader's current swap state (e.g. awaiting confirmation after a swap).
+    State,
+    /// Confirm the running image is good, cancelling the bootloader's revert-on-reset.
+    Confirm,
+}
+
+/// Commands for the Si5351-class I2C clock generator driving the ASIC reference clock.
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum ClockCommand {
+    /// Configure `output` for `freq_hz`, then sequence `asic_resetn` once the PLL is locked.
+    SetFrequency { output: u8, freq_hz: u32 },
+    Enable(u8),
+    Disable(u8),
+    /// Re-latch the PLL dividers. Required after any PLL (not just Multisynth) change.
+    PllReset,
+}
+
+/// The actually-achieved frequency and divider values for a [`ClockCommand::SetFrequency`],
+/// so the host can verify lock instead of trusting the request it sent.
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub struct ClockLock {
+    pub freq_hz: u32,
+    pub pll_mult_a: u32,
+    pub pll_mult_b: u32,
+    pub pll_mult_c: u32,
+    pub ms_div_d: u32,
+    pub ms_div_e: u32,
+    pub ms_div_f: u32,
+}
+
+/// Diagnostic and recovery commands that don't belong to a particular peripheral.
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum SystemCommand {
+    /// Report why the chip last reset.
+    ResetCause,
+    /// Reset straight into the RP2040 ROM USB bootloader so the host can push a UF2
+    /// without the user touching the BOOTSEL button.
+    RebootToBootsel,
+    /// Reset back into this same firmware image.
+    SoftwareReset,
+}
+
+/// Why the chip last came out of reset, decoded from the watchdog's hardware
+/// reason register plus the software intent flags stashed in its scratch registers.
+#[derive(Debug, Clone, Copy, defmt::Format, Serialize, Deserialize)]
+pub enum ResetCause {
+    PowerOn,
+    WatchdogTimeout,
+    SoftwareRequested,
+    DfuRequested,
+}
+
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub struct DeviceResponse {
+    pub id: i8,
+    pub result: Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError>,
+}
+
+#[derive(Debug, Clone, defmt::Format, Serialize, Deserialize)]
+pub enum DeviceError {
+    Timeout,
+    Invalid,
+    BufferOverflow,
+    CrcMismatch,
+    Message(String<64>),
+}
@@ -0,0 +1,186 @@
+use embassy_time::Timer;
+use embedded_hal_async::i2c::I2c as _;
+use heapless::Vec;
+use protocol::{ClockCommand, ClockLock, DeviceError, MAX_PAYLOAD_LEN};
+
+use super::{display, Controller, ControllerCommand};
+
+/// 7-bit I2C address of the Si5351-class clock generator.
+const SI5351_ADDR: u8 = 0x60;
+
+/// Board reference crystal. Si5351 parts in this family also support 27 MHz; swap this
+/// constant (and re-derive `fvco`/`fout` below, which only assume the ratio works out)
+/// if a 27 MHz part is fitted.
+const XTAL_HZ: u64 = 25_000_000;
+
+/// `fvco = fxtal * (a + b/c)` must land in this range for the PLL to lock.
+const FVCO_MIN_HZ: u64 = 600_000_000;
+const FVCO_MAX_HZ: u64 = 900_000_000;
+
+/// The output Multisynth divider `d` is only valid in this range, which bounds the
+/// output frequencies `plan()` can turn into a divider without over/underflowing.
+const MS_DIV_MIN: u64 = 4;
+const MS_DIV_MAX: u64 = 900;
+// Ceiling division: a floor-divided bound would let through a `freq_hz` just below it
+// whose `ms_whole = ceil(FVCO_MIN_HZ / freq_hz)` (see `plan`) comes out to `MS_DIV_MAX + 1`,
+// overflowing the Multisynth divider.
+const MIN_OUTPUT_HZ: u32 = ((FVCO_MIN_HZ + MS_DIV_MAX - 1) / MS_DIV_MAX) as u32;
+const MAX_OUTPUT_HZ: u32 = (FVCO_MAX_HZ / MS_DIV_MIN) as u32;
+
+/// Si5351 register map (subset).
+mod reg {
+    pub const CLK_CONTROL: u8 = 16; // CLK0..CLK7 at 16..=23
+    pub const MSNA_PARAMS: u8 = 26; // PLLA Multisynth, 8 bytes
+    pub const MS0_PARAMS: u8 = 42; // output Multisynth 0, 8 bytes each
+    pub const PLL_RESET: u8 = 177;
+}
+
+impl ControllerCommand for ClockCommand {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+        match self {
+            ClockCommand::SetFrequency { output, freq_hz } => set_frequency(controller, *output, *freq_hz).await,
+            ClockCommand::Enable(output) => {
+                set_output_enabled(controller, *output, true).await?;
+                Ok(Vec::new())
+            }
+            ClockCommand::Disable(output) => {
+                set_output_enabled(controller, *output, false).await?;
+                Ok(Vec::new())
+            }
+            ClockCommand::PllReset => {
+                pll_reset(controller).await?;
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+/// Multisynth divider `d + e/f`, `f` fixed at the Si5351's 20-bit denominator.
+struct Divider {
+    whole: u32,
+    num: u32,
+    denom: u32,
+}
+
+fn divide(numerator: u64, denominator: u64) -> Divider {
+    const DENOM: u64 = 1_048_575; // 2^20 - 1
+    let whole = numerator / denominator;
+    let rem = numerator % denominator;
+    let num = (rem * DENOM) / denominator;
+    Divider { whole: whole as u32, num: num as u32, denom: DENOM as u32 }
+}
+
+/// Find an output Multisynth divider that keeps `fvco` in the PLL's lock range, then the
+/// matching PLL feedback divider for that `fvco`.
+fn plan(freq_hz: u32) -> (u64, Divider, Divider) {
+    let freq_hz = freq_hz as u64;
+
+    let ms_whole = ((FVCO_MIN_HZ + freq_hz - 1) / freq_hz).max(4);
+    let fvco = (freq_hz * ms_whole).clamp(FVCO_MIN_HZ, FVCO_MAX_HZ);
+
+    let pll = divide(fvco, XTAL_HZ);
+    let ms = divide(fvco, freq_hz);
+
+    (fvco, pll, ms)
+}
+
+/// Encode a Multisynth/PLL `(whole, num, denom)` triple into the Si5351's 8-register
+/// `P1`/`P2`/`P3` parameter block.
+fn params(div: &Divider) -> [u8; 8] {
+    let p1 = 128 * div.whole - 512 + (128 * div.num) / div.denom;
+    let p2 = 128 * div.num - div.denom * ((128 * div.num) / div.denom);
+    let p3 = div.denom;
+
+    [
+        (p3 >> 8) as u8,
+        p3 as u8,
+        (p1 >> 16) as u8,
+        (p1 >> 8) as u8,
+        p1 as u8,
+        (((p3 >> 16) as u8) << 4) | ((p2 >> 16) as u8),
+        (p2 >> 8) as u8,
+        p2 as u8,
+    ]
+}
+
+async fn write_params(controller: &mut Controller, start_reg: u8, div: &Divider) -> Result<(), DeviceError> {
+    let payload = params(div);
+    let mut buf = [0u8; 9];
+    buf[0] = start_reg;
+    buf[1..].copy_from_slice(&payload);
+    controller.i2c.write(SI5351_ADDR, &buf).await.map_err(|_| DeviceError::Invalid)
+}
+
+async fn set_output_enabled(controller: &mut Controller, output: u8, enabled: bool) -> Result<(), DeviceError> {
+    validate_output(output)?;
+
+    // bit 7 high powers the output stage down; bits 3:2 = 0b11 select the output's own
+    // Multisynth as its source, with MS_INT (bit 6) clear so it runs in fractional mode.
+    let control = if enabled { 0b0000_1100 } else { 0b1000_0000 };
+    controller
+        .i2c
+        .write(SI5351_ADDR, &[reg::CLK_CONTROL + output, control])
+        .await
+        .map_err(|_| DeviceError::Invalid)
+}
+
+/// The Si5351 only has CLK0-7; reject anything else before it's used in register
+/// arithmetic like `reg::CLK_CONTROL + output`, which would otherwise silently wrap.
+fn validate_output(output: u8) -> Result<(), DeviceError> {
+    if output > 7 {
+        return Err(DeviceError::Invalid);
+    }
+    Ok(())
+}
+
+async fn pll_reset(controller: &mut Controller) -> Result<(), DeviceError> {
+    controller.i2c.write(SI5351_ADDR, &[reg::PLL_RESET, 0xA0]).await.map_err(|_| DeviceError::Invalid)
+}
+
+/// Program `output` for `freq_hz`, holding `asic_resetn` low until the PLL has had time
+/// to lock so the ASIC never sees a clock edge before it's stable.
+async fn set_frequency(controller: &mut Controller, output: u8, freq_hz: u32) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+    validate_output(output)?;
+
+    if !(MIN_OUTPUT_HZ..=MAX_OUTPUT_HZ).contains(&freq_hz) {
+        return Err(DeviceError::Invalid);
+    }
+
+    controller.gpio.asic_pwr_en.set_high();
+    display::update(|status| status.asic_pwr_en = true).await;
+    controller.gpio.asic_resetn.set_low();
+    display::update(|status| status.asic_resetn = false).await;
+
+    let (_, pll, ms) = plan(freq_hz);
+
+    write_params(controller, reg::MSNA_PARAMS, &pll).await?;
+    write_params(controller, reg::MS0_PARAMS + 8 * output, &ms).await?;
+    set_output_enabled(controller, output, true).await?;
+    pll_reset(controller).await?;
+
+    // Datasheet-recommended settling time after a PLL reset before the clock is trusted.
+    Timer::after_millis(10).await;
+    controller.gpio.asic_resetn.set_high();
+    display::update(|status| status.asic_resetn = true).await;
+
+    // Recompute the output from the dividers as programmed: `divide` truncates the
+    // fractional numerators, so this can differ slightly from the requested `freq_hz`.
+    let fvco_achieved = XTAL_HZ * (pll.whole as u64 * pll.denom as u64 + pll.num as u64) / pll.denom as u64;
+    let freq_achieved = fvco_achieved * ms.denom as u64 / (ms.whole as u64 * ms.denom as u64 + ms.num as u64);
+
+    let achieved = ClockLock {
+        freq_hz: freq_achieved as u32,
+        pll_mult_a: pll.whole,
+        pll_mult_b: pll.num,
+        pll_mult_c: pll.denom,
+        ms_div_d: ms.whole,
+        ms_div_e: ms.num,
+        ms_div_f: ms.denom,
+    };
+
+    let mut out = Vec::new();
+    let mut buf = [0u8; MAX_PAYLOAD_LEN];
+    let encoded = postcard::to_slice(&achieved, &mut buf).map_err(|_| DeviceError::BufferOverflow)?;
+    out.extend_from_slice(encoded).map_err(|_| DeviceError::BufferOverflow)?;
+    Ok(out)
+}
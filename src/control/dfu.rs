@@ -0,0 +1,122 @@
+use embassy_boot_rp::{AlignedBuffer, FirmwareUpdater, FirmwareUpdaterConfig};
+use embassy_rp::flash::{Blocking, Flash};
+use embassy_rp::peripherals::FLASH;
+use embassy_rp::watchdog::Watchdog;
+use heapless::Vec;
+use protocol::{DeviceError, DfuCommand, MAX_PAYLOAD_LEN};
+
+use super::{Controller, ControllerCommand};
+use crate::FLASH_SIZE;
+
+pub type FlashDriver = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+
+/// Reboot-survives intent flag, stashed in watchdog scratch register 1 so `main` can
+/// tell a post-DFU-commit reset apart from a plain watchdog timeout.
+pub const DFU_REBOOT_SCRATCH: u32 = 0xd0f0_d0f0;
+
+/// Drives an `embassy-boot-rp` firmware update through the control channel: the host
+/// streams `DfuCommand::Chunk`s into the DFU partition, then `DfuCommand::Commit`
+/// verifies the CRC it declared up front and hands off to the bootloader.
+pub struct Dfu {
+    updater: FirmwareUpdater<'static, FlashDriver, FlashDriver>,
+    aligned: AlignedBuffer<4>,
+    expected: Option<(u32, u32)>, // (total_len, crc32)
+    /// Offset the next `Chunk` must start at. A resent or out-of-order chunk (the
+    /// realistic failure mode on a USB control channel) won't match this and is
+    /// rejected, rather than silently double-counted into `crc`/the byte total.
+    next_offset: u32,
+    crc: u32,
+}
+
+impl Dfu {
+    pub fn new(flash: FlashDriver) -> Self {
+        static STATE_FLASH: static_cell::StaticCell<core::cell::RefCell<FlashDriver>> = static_cell::StaticCell::new();
+        let state_flash = STATE_FLASH.init(core::cell::RefCell::new(flash));
+        let config = FirmwareUpdaterConfig::from_linkerfile_blocking(state_flash, state_flash);
+
+        Self {
+            updater: FirmwareUpdater::new(config),
+            aligned: AlignedBuffer([0; 4]),
+            expected: None,
+            next_offset: 0,
+            crc: 0,
+        }
+    }
+}
+
+impl ControllerCommand for DfuCommand {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+        let dfu = &mut controller.dfu;
+
+        match self {
+            DfuCommand::Begin { total_len, crc32 } => {
+                dfu.expected = Some((*total_len, *crc32));
+                dfu.next_offset = 0;
+                dfu.crc = 0;
+                Ok(Vec::new())
+            }
+
+            DfuCommand::Chunk { offset, data } => {
+                if dfu.expected.is_none() {
+                    return Err(DeviceError::Invalid);
+                }
+
+                // A chunk only counts if it's the one we're expecting next. This rejects
+                // resends of a chunk we already wrote and gaps from one we haven't yet,
+                // so `crc`/`next_offset` always reflect exactly what's in the partition.
+                if *offset != dfu.next_offset {
+                    return Err(DeviceError::Invalid);
+                }
+
+                dfu.updater.write_firmware(*offset as usize, data, &mut dfu.aligned).await.map_err(|_| DeviceError::Invalid)?;
+
+                dfu.crc = crc32_update(dfu.crc, data);
+                dfu.next_offset += data.len() as u32;
+                Ok(Vec::new())
+            }
+
+            DfuCommand::Commit => {
+                let (total_len, expected_crc) = dfu.expected.take().ok_or(DeviceError::Invalid)?;
+
+                if dfu.next_offset != total_len || dfu.crc != expected_crc {
+                    return Err(DeviceError::CrcMismatch);
+                }
+
+                dfu.updater.mark_updated(&mut dfu.aligned).await.map_err(|_| DeviceError::Invalid)?;
+
+                // The swap itself happens on the next boot; force one via the watchdog
+                // rather than requiring a debug probe or power cycle.
+                let mut watchdog = unsafe { Watchdog::new(embassy_rp::peripherals::WATCHDOG::steal()) };
+                watchdog.set_scratch(1, DFU_REBOOT_SCRATCH);
+                watchdog.trigger_reset();
+
+                Ok(Vec::new())
+            }
+
+            DfuCommand::State => {
+                let state = dfu.updater.get_state(&mut dfu.aligned).await.map_err(|_| DeviceError::Invalid)?;
+                let mut out = Vec::new();
+                out.push(state as u8).map_err(|_| DeviceError::BufferOverflow)?;
+                Ok(out)
+            }
+
+            DfuCommand::Confirm => {
+                dfu.updater.mark_booted(&mut dfu.aligned).await.map_err(|_| DeviceError::Invalid)?;
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+/// CRC32 (IEEE 802.3 polynomial, reflected), matching what the host computes over the
+/// whole image before the first `DfuBegin`.
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
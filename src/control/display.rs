@@ -0,0 +1,105 @@
+use core::fmt::Write as _;
+
+use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{Duration, Ticker};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::Text,
+};
+use heapless::String;
+use ssd1306::{mode::DisplayConfig, prelude::*, I2CDisplayInterface, Ssd1306};
+
+/// Status reported by the display task. Each field is owned by whichever task observes
+/// it directly; see [`update`].
+#[derive(Clone, Copy)]
+pub struct Status {
+    pub control_connected: bool,
+    pub uart_connected: bool,
+    pub asic_pwr_en: bool,
+    pub asic_resetn: bool,
+    pub last_command_id: i8,
+    pub last_command_ok: bool,
+    pub uart_rx_bytes: u32,
+}
+
+impl Status {
+    const fn new() -> Self {
+        Self {
+            control_connected: false,
+            uart_connected: false,
+            asic_pwr_en: false,
+            asic_resetn: false,
+            last_command_id: 0,
+            last_command_ok: true,
+            uart_rx_bytes: 0,
+        }
+    }
+
+    pub fn note_command(&mut self, id: i8, ok: bool) {
+        self.last_command_id = id;
+        self.last_command_ok = ok;
+    }
+
+    /// Roll `bytes` more into the running UART receive counter, saturating rather than
+    /// wrapping back to a misleadingly small number once the ASIC console gets chatty.
+    pub fn note_uart_rx(&mut self, bytes: usize) {
+        self.uart_rx_bytes = self.uart_rx_bytes.saturating_add(bytes as u32);
+    }
+}
+
+static STATE: Mutex<ThreadModeRawMutex, Status> = Mutex::new(Status::new());
+static CHANGED: Signal<ThreadModeRawMutex, Status> = Signal::new();
+
+/// Mutate the shared status under its lock and wake the display task with the result.
+/// Called from [`super::Controller::run`] and the command handlers it dispatches to,
+/// [`super::usb_task`], and the UART bridge in [`crate::uart`], as their own bit of
+/// state changes, so no single task needs to know the others' fields.
+pub async fn update(f: impl FnOnce(&mut Status)) {
+    let mut status = STATE.lock().await;
+    f(&mut status);
+    CHANGED.signal(*status);
+}
+
+/// Renders [`Status`] to the SSD1306 whenever it changes, falling back to a periodic
+/// refresh so a stalled updater doesn't leave stale text on screen indefinitely.
+#[embassy_executor::task]
+pub async fn task(i2c: I2cDevice<'static, ThreadModeRawMutex, super::super::I2cDriver>) {
+    let interface = I2CDisplayInterface::new(i2c);
+    let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0).into_buffered_graphics_mode();
+    let _ = display.init();
+
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let mut ticker = Ticker::every(Duration::from_millis(500));
+    let mut line = String::<32>::new();
+
+    loop {
+        let status = match embassy_time::with_timeout(Duration::from_millis(500), CHANGED.wait()).await {
+            Ok(status) => status,
+            Err(_) => *STATE.lock().await,
+        };
+
+        display.clear(BinaryColor::Off).ok();
+
+        line.clear();
+        let _ = write!(line, "ctl {} uart {}", status.control_connected as u8, status.uart_connected as u8);
+        let _ = Text::new(&line, Point::new(0, 10), style).draw(&mut display);
+
+        line.clear();
+        let _ = write!(line, "pwr {} rst {}", status.asic_pwr_en as u8, status.asic_resetn as u8);
+        let _ = Text::new(&line, Point::new(0, 24), style).draw(&mut display);
+
+        line.clear();
+        let _ = write!(line, "cmd {} ok {}", status.last_command_id, status.last_command_ok as u8);
+        let _ = Text::new(&line, Point::new(0, 38), style).draw(&mut display);
+
+        line.clear();
+        let _ = write!(line, "uart rx {}", status.uart_rx_bytes);
+        let _ = Text::new(&line, Point::new(0, 52), style).draw(&mut display);
+
+        let _ = display.flush();
+        ticker.next().await;
+    }
+}
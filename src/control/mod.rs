@@ -1,153 +1,176 @@
 use defmt::info;
 
+use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
 use embassy_futures::join::join;
 use embassy_rp::usb;
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
-use embassy_time::{Duration, TimeoutError};
 use embassy_usb::{
     class::cdc_acm::{CdcAcmClass, Receiver, Sender},
     driver::EndpointError,
 };
+use embedded_hal_async::i2c::I2c as _;
 use heapless::Vec;
+use postcard::accumulator::{CobsAccumulator, FeedResult};
+use protocol::{CommandKind, DeviceError, DeviceResponse, GpioCommand, HostCommand, I2cCommand, LedCommand, MAX_FRAME_LEN, MAX_PAYLOAD_LEN};
 
 pub mod i2c;
-const I2C_COMMAND: u8 = 5;
-
 pub mod gpio;
-const GPIO_COMMAND: u8 = 6;
-
 pub mod led;
-const LED_COMMAND: u8 = 8;
-
-
-#[derive(defmt::Format)]
-struct Command {
-    id: i8,
-    bus: u8,
-    inner: CommandInner,
+pub mod dfu;
+pub mod display;
+pub mod clock;
+pub mod system;
+
+/// The I2C1 bus, shared between the command handler and the status [`display`] task.
+pub type SharedI2c = embassy_sync::mutex::Mutex<ThreadModeRawMutex, super::I2cDriver>;
+
+/// A decoded frame or the error encountered while decoding one. Framing errors carry no
+/// command id (the id lives inside the postcard payload we failed to recover), so they're
+/// reported against id `-1`, matching the "unsolicited" id the host never assigns.
+enum Frame {
+    Command(HostCommand),
+    Error(DeviceError),
 }
 
-#[derive(defmt::Format)]
-enum CommandInner {
-    I2c(i2c::Command),
-    Gpio(gpio::Command),
-    Led(led::Command),
-    Error(CommandError),
+pub trait ControllerCommand {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError>;
 }
 
-impl Command {
-    fn from_bytes(buf: &[u8]) -> Result<Self, CommandError> {
-        let id = buf[0] as i8;
-        match buf[2] {
-            I2C_COMMAND => Ok(Self {
-                id,
-                bus: buf[1],
-                inner: CommandInner::I2c(i2c::Command::from_bytes(&buf[3..])?),
-            }),
-            GPIO_COMMAND => Ok(Self {
-                id,
-                bus: buf[1],
-                inner: CommandInner::Gpio(gpio::Command::from_bytes(&buf[3..])?),
-            }),
-            LED_COMMAND => Ok(Self {
-                id,
-                bus: buf[1],
-                inner: CommandInner::Led(led::Command::from_bytes(&buf[3..])?),
-            }),
-            _ => Err(CommandError::Invalid),
+impl ControllerCommand for CommandKind {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+        match self {
+            CommandKind::I2c(cmd) => cmd.handle(controller).await,
+            CommandKind::Gpio(cmd) => cmd.handle(controller).await,
+            CommandKind::Led(cmd) => cmd.handle(controller).await,
+            CommandKind::Dfu(cmd) => cmd.handle(controller).await,
+            CommandKind::Clock(cmd) => cmd.handle(controller).await,
+            CommandKind::System(cmd) => cmd.handle(controller).await,
         }
     }
 }
 
-#[derive(defmt::Format)]
-pub enum CommandError {
-    Timeout,               // 0x10
-    Invalid,               // 0x11
-    BufferOverflow,        // 0x12
-    Message(&'static str), // 0xff
-}
-
-impl CommandError {
-    fn to_bytes(&self) -> Vec<u8, 260> {
-        let mut buf = Vec::<u8, 260>::new();
-        buf.extend_from_slice(&[0x00, 0x00, 0xff]).unwrap();
+impl ControllerCommand for I2cCommand {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+        let mut out = Vec::new();
 
         match self {
-            CommandError::Timeout => {
-                buf.push(0x10).unwrap();
+            I2cCommand::Write { addr, data } => {
+                controller.i2c.write(*addr, data).await.map_err(|_| DeviceError::Invalid)?;
+            }
+            I2cCommand::Read { addr, len } => {
+                if *len as usize > MAX_PAYLOAD_LEN {
+                    return Err(DeviceError::BufferOverflow);
+                }
+                let mut buf = [0u8; MAX_PAYLOAD_LEN];
+                let buf = &mut buf[..*len as usize];
+                controller.i2c.read(*addr, buf).await.map_err(|_| DeviceError::Invalid)?;
+                out.extend_from_slice(buf).map_err(|_| DeviceError::BufferOverflow)?;
             }
-            CommandError::Invalid => {
-                buf.push(0x11).unwrap();
+            I2cCommand::WriteRead { addr, data, read_len } => {
+                if *read_len as usize > MAX_PAYLOAD_LEN {
+                    return Err(DeviceError::BufferOverflow);
+                }
+                let mut buf = [0u8; MAX_PAYLOAD_LEN];
+                let buf = &mut buf[..*read_len as usize];
+                controller.i2c.write_read(*addr, data, buf).await.map_err(|_| DeviceError::Invalid)?;
+                out.extend_from_slice(buf).map_err(|_| DeviceError::BufferOverflow)?;
             }
-            CommandError::BufferOverflow => {
-                buf.push(0x12).unwrap();
+        }
+
+        Ok(out)
+    }
+}
+
+impl ControllerCommand for GpioCommand {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+        match self {
+            GpioCommand::SetAsicPwrEn(level) => {
+                if *level { controller.gpio.asic_pwr_en.set_high() } else { controller.gpio.asic_pwr_en.set_low() }
+                display::update(|status| status.asic_pwr_en = *level).await;
             }
-            CommandError::Message(msg) => {
-                buf.push(0xff).unwrap();
-                buf.extend_from_slice(msg.as_bytes()).unwrap();
+            GpioCommand::SetAsicResetn(level) => {
+                if *level { controller.gpio.asic_resetn.set_high() } else { controller.gpio.asic_resetn.set_low() }
+                display::update(|status| status.asic_resetn = *level).await;
             }
+            GpioCommand::ReadPins => {}
+        }
+
+        let mut out = Vec::new();
+        if matches!(self, GpioCommand::ReadPins) {
+            out.push(controller.gpio.asic_pwr_en.is_set_high() as u8).unwrap();
+            out.push(controller.gpio.asic_resetn.is_set_high() as u8).unwrap();
+        }
+
+        Ok(out)
+    }
+}
+
+impl ControllerCommand for LedCommand {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+        match self {
+            LedCommand::Set { r, g, b } => controller.led.set(*r, *g, *b).await,
+            LedCommand::Off => controller.led.set(0, 0, 0).await,
         }
 
-        let len = (buf.len() as u16).to_le_bytes();
-        buf[0..2].clone_from_slice(&len);
-        buf
+        Ok(Vec::new())
     }
 }
 
-static COMMAND_CHANNEL: Channel<ThreadModeRawMutex, Command, 8> = Channel::new();
+static COMMAND_CHANNEL: Channel<ThreadModeRawMutex, Frame, 8> = Channel::new();
 
 pub struct Controller {
     tx: Sender<'static, super::UsbDriver>,
-    i2c: super::I2cDriver,
+    i2c: I2cDevice<'static, ThreadModeRawMutex, super::I2cDriver>,
     gpio: gpio::Pins<'static>,
     led: led::Led<'static>,
-}
-
-pub trait ControllerCommand {
-    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, 256>, CommandError>;
+    dfu: dfu::Dfu,
+    reset_cause: protocol::ResetCause,
 }
 
 impl Controller {
     pub async fn run(&mut self) {
         loop {
-            let cmd = COMMAND_CHANNEL.receive().await;
-            let res = match cmd.inner {
-                CommandInner::I2c(cmd) => cmd.handle(self).await,
-                CommandInner::Gpio(cmd) => cmd.handle(self).await,
-                CommandInner::Led(cmd) => cmd.handle(self).await,
-                CommandInner::Error(err) => Err(err),
-            };
+            let frame = COMMAND_CHANNEL.receive().await;
 
-            let buf = match res {
-                Ok(res) => {
-                    let mut buf = Vec::<u8, 260>::new();
-                    buf.extend_from_slice(&(res.len() as u16).to_le_bytes()).unwrap();
-                    buf.push(cmd.id as u8).unwrap();
-                    buf.extend_from_slice(&res).unwrap();
-                    buf
-                }
-                Err(err) => {
-                    let mut buf = err.to_bytes();
-                    buf[2] = cmd.id as u8;
-                    buf
-                }
+            let (id, result) = match frame {
+                Frame::Command(cmd) => (cmd.id, cmd.inner.handle(self).await),
+                Frame::Error(err) => (-1, Err(err)),
             };
 
-            let _ = self.tx.write_packet(&buf).await;
+            display::update(|status| status.note_command(id, result.is_ok())).await;
+
+            let response = DeviceResponse { id, result };
+
+            let mut frame_buf = [0u8; MAX_FRAME_LEN];
+            match postcard::to_slice_cobs(&response, &mut frame_buf) {
+                Ok(encoded) => {
+                    let _ = self.tx.write_packet(encoded).await;
+                }
+                Err(err) => defmt::error!("Control: response for id {} didn't fit a frame: {}", id, defmt::Debug2Format(&err)),
+            }
         }
     }
 }
 
 #[embassy_executor::task]
-pub async fn usb_task(class: CdcAcmClass<'static, super::UsbDriver>, i2c: super::I2cDriver, gpio: gpio::Pins<'static>, led: led::Led<'static>) -> ! {
+pub async fn usb_task(
+    class: CdcAcmClass<'static, super::UsbDriver>,
+    i2c: I2cDevice<'static, ThreadModeRawMutex, super::I2cDriver>,
+    gpio: gpio::Pins<'static>,
+    led: led::Led<'static>,
+    dfu: dfu::Dfu,
+    reset_cause: protocol::ResetCause,
+) -> ! {
     let (tx, mut rx, mut _ctrl) = class.split_with_control();
-    let mut controller = Controller { tx, i2c, gpio, led };
+    let mut controller = Controller { tx, i2c, gpio, led, dfu, reset_cause };
 
     loop {
         rx.wait_connection().await;
         info!("Control: Connected");
+        display::update(|status| status.control_connected = true).await;
         let _ = join(pipe_usb_read(&mut rx), controller.run()).await;
         info!("Control: Disconnected");
+        display::update(|status| status.control_connected = false).await;
     }
 }
 
@@ -164,48 +187,37 @@ impl From<EndpointError> for ControlTaskError {
     }
 }
 
+/// Reads COBS-delimited, postcard-encoded [`HostCommand`] frames off the control CDC
+/// endpoint and forwards them to [`Controller::run`].
+///
+/// Framing no longer tracks a byte offset: `CobsAccumulator` buffers bytes until it sees
+/// the `0x00` frame delimiter, COBS-decodes the group in place, and hands back whatever
+/// postcard makes of it. A dropped or corrupted byte only ever costs the one frame it
+/// falls in — the next `0x00` realigns the reader, instead of desyncing it permanently.
 async fn pipe_usb_read<'d, T: usb::Instance + 'd>(rx: &mut Receiver<'d, usb::Driver<'d, T>>) -> Result<(), ControlTaskError> {
-    let mut buf = [0; 4098];
+    let mut acc = CobsAccumulator::<MAX_FRAME_LEN>::new();
+    let mut buf = [0; 64];
 
     loop {
-        let mut num_read: usize = 0;
-
-        'read: loop {
-            let read = rx.read_packet(&mut buf[num_read..]);
-
-            match embassy_time::with_timeout(Duration::from_millis(4), read).await {
-                Ok(Ok(n)) => {
-                    num_read += n;
-
-                    if num_read >= 5 {
-                        let to_read = u16::from_le_bytes(buf[0..2].try_into().unwrap()) as usize;
-
-                        if num_read >= to_read {
-                            let excess = num_read - to_read;
-
-                            match Command::from_bytes(&buf[2..to_read]) {
-                                Ok(cmd) => COMMAND_CHANNEL.send(cmd).await,
-                                Err(err) => COMMAND_CHANNEL.send(Command { id: -1, bus: 0, inner: CommandInner::Error(err) }).await,
-                            }
-
-                            let mut new_buf = [0; 4098];
-                            new_buf[..excess].clone_from_slice(&buf[to_read..to_read + excess]);
-
-                            num_read = excess;
-                            buf = new_buf;
-                        }
-                    }
+        let n = rx.read_packet(&mut buf).await?;
+        let mut window = &buf[..n];
+
+        while !window.is_empty() {
+            window = match acc.feed::<HostCommand>(window) {
+                FeedResult::Consumed => break,
+                FeedResult::OverFull(remaining) => {
+                    COMMAND_CHANNEL.send(Frame::Error(DeviceError::BufferOverflow)).await;
+                    remaining
                 }
-
-                Ok(Err(err)) => {
-                    return Err(err.into());
+                FeedResult::DeserError(remaining) => {
+                    COMMAND_CHANNEL.send(Frame::Error(DeviceError::Invalid)).await;
+                    remaining
                 }
-
-                Err(TimeoutError) => {
-                    let _error = CommandError::Timeout;
-                    break 'read;
+                FeedResult::Success { data, remaining } => {
+                    COMMAND_CHANNEL.send(Frame::Command(data)).await;
+                    remaining
                 }
-            }
+            };
         }
     }
 }
@@ -0,0 +1,49 @@
+use embassy_rp::{pac, rom_data};
+use heapless::Vec;
+use protocol::{DeviceError, MAX_PAYLOAD_LEN, ResetCause, SystemCommand};
+
+use super::{dfu, Controller, ControllerCommand};
+
+/// Scratch register 0 intent flag: the previous boot reset itself on purpose via
+/// `SystemCommand::SoftwareReset`, as opposed to the watchdog biting unexpectedly.
+/// Register 1 is [`dfu::DFU_REBOOT_SCRATCH`], set before the post-commit DFU reset.
+pub const SOFTWARE_RESET_SCRATCH: u32 = 0x50f7_50f7;
+
+/// Decode why the chip last came out of reset. Must run before `main` clears the
+/// scratch registers for the next boot.
+pub fn reset_cause() -> ResetCause {
+    let scratch0 = pac::WATCHDOG.scratch0().read();
+    let scratch1 = pac::WATCHDOG.scratch1().read();
+    let reason = pac::WATCHDOG.reason().read();
+
+    if scratch1 == dfu::DFU_REBOOT_SCRATCH {
+        ResetCause::DfuRequested
+    } else if scratch0 == SOFTWARE_RESET_SCRATCH {
+        ResetCause::SoftwareRequested
+    } else if reason.timer() || reason.force() {
+        ResetCause::WatchdogTimeout
+    } else {
+        ResetCause::PowerOn
+    }
+}
+
+impl ControllerCommand for SystemCommand {
+    async fn handle(&self, controller: &mut Controller) -> Result<Vec<u8, MAX_PAYLOAD_LEN>, DeviceError> {
+        match self {
+            SystemCommand::ResetCause => {
+                let mut out = Vec::new();
+                out.push(controller.reset_cause as u8).map_err(|_| DeviceError::BufferOverflow)?;
+                Ok(out)
+            }
+
+            // Neither reboot path returns: the RP2040 resets before the response would
+            // ever reach `Controller::run`'s tx.write_packet.
+            SystemCommand::RebootToBootsel => unsafe { rom_data::reset_to_usb_boot(0, 0) },
+
+            SystemCommand::SoftwareReset => {
+                pac::WATCHDOG.scratch0().write_value(SOFTWARE_RESET_SCRATCH);
+                cortex_m::peripheral::SCB::sys_reset();
+            }
+        }
+    }
+}
@@ -60,8 +60,13 @@ fn serial_number() -> &'static str {
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
+    // Read the previous boot's intent flags before clearing them for this one.
+    let reset_cause = control::system::reset_cause();
+    defmt::info!("Reset cause: {}", reset_cause);
+
     let mut watchdog = embassy_rp::watchdog::Watchdog::new(p.WATCHDOG);
     watchdog.set_scratch(0, 0);
+    watchdog.set_scratch(1, 0);
     watchdog.feed();
 
     let usb_driver = usb::Driver::new(p.USB, Irqs);
@@ -111,10 +116,12 @@ async fn main(spawner: Spawner) {
         embassy_rp::uart::BufferedUart::new(uart, Irqs, tx_pin, rx_pin, tx_buf, rx_buf, Default::default())
     };
 
-    let i2c = {
+    let i2c_bus = {
         let sda = p.PIN_14;
         let scl = p.PIN_15;
-        embassy_rp::i2c::I2c::new_async(p.I2C1, scl, sda, Irqs, Default::default())
+        let i2c = embassy_rp::i2c::I2c::new_async(p.I2C1, scl, sda, Irqs, Default::default());
+        static I2C_BUS: StaticCell<control::SharedI2c> = StaticCell::new();
+        I2C_BUS.init(embassy_sync::mutex::Mutex::new(i2c))
     };
 
     let gpio_pins = control::gpio::Pins {
@@ -125,9 +132,22 @@ async fn main(spawner: Spawner) {
     let pio::Pio { mut common, sm0, .. } = pio::Pio::new(p.PIO0, Irqs);
     let led = control::led::Led::new(&mut common, sm0, p.PIN_1, p.DMA_CH0.into());
 
+    let dfu = {
+        let flash = flash::Flash::<_, flash::Blocking, FLASH_SIZE>::new_blocking(p.FLASH);
+        control::dfu::Dfu::new(flash)
+    };
+
     unwrap!(spawner.spawn(usb_task(builder.build())));
-    unwrap!(spawner.spawn(control::usb_task(control_class, i2c, gpio_pins, led)));
+    unwrap!(spawner.spawn(control::usb_task(
+        control_class,
+        embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice::new(i2c_bus),
+        gpio_pins,
+        led,
+        dfu,
+        reset_cause
+    )));
     unwrap!(spawner.spawn(uart::usb_task(asic_uart_class, asic_uart)));
+    unwrap!(spawner.spawn(control::display::task(embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice::new(i2c_bus))));
 
     loop {
         watchdog.feed();
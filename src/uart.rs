@@ -0,0 +1,61 @@
+use defmt::info;
+
+use embassy_futures::join::join;
+use embassy_rp::uart::{BufferedUart, BufferedUartRx, BufferedUartTx};
+use embassy_usb::{
+    class::cdc_acm::{CdcAcmClass, Receiver, Sender},
+    driver::EndpointError,
+};
+use embedded_io_async::{Read, Write};
+
+use crate::control::display;
+
+enum UartTaskError {
+    Disconnected,
+}
+
+impl From<EndpointError> for UartTaskError {
+    fn from(val: EndpointError) -> Self {
+        match val {
+            EndpointError::BufferOverflow => panic!("Buffer overflow"),
+            EndpointError::Disabled => UartTaskError::Disconnected {},
+        }
+    }
+}
+
+/// Bridges the ASIC UART straight through to its own CDC-ACM endpoint, so a host
+/// terminal attached to this interface talks directly to the ASIC's serial console.
+#[embassy_executor::task]
+pub async fn usb_task(class: CdcAcmClass<'static, super::UsbDriver>, uart: BufferedUart<'static, super::AsicUart>) -> ! {
+    let (tx, mut rx, mut _ctrl) = class.split_with_control();
+    let (uart_rx, uart_tx) = uart.split();
+
+    loop {
+        rx.wait_connection().await;
+        info!("UART: Connected");
+        display::update(|status| status.uart_connected = true).await;
+        let _ = join(usb_to_uart(&mut rx, uart_tx), uart_to_usb(uart_rx, tx)).await;
+        info!("UART: Disconnected");
+        display::update(|status| status.uart_connected = false).await;
+    }
+}
+
+async fn usb_to_uart(rx: &mut Receiver<'static, super::UsbDriver>, mut uart_tx: BufferedUartTx<'static, super::AsicUart>) -> Result<(), UartTaskError> {
+    let mut buf = [0; 64];
+    loop {
+        let n = rx.read_packet(&mut buf).await?;
+        let _ = uart_tx.write_all(&buf[..n]).await;
+    }
+}
+
+async fn uart_to_usb(mut uart_rx: BufferedUartRx<'static, super::AsicUart>, mut tx: Sender<'static, super::UsbDriver>) -> Result<(), UartTaskError> {
+    let mut buf = [0; 64];
+    loop {
+        let n = uart_rx.read(&mut buf).await.map_err(|_| UartTaskError::Disconnected)?;
+        if n == 0 {
+            continue;
+        }
+        display::update(|status| status.note_uart_rx(n)).await;
+        tx.write_packet(&buf[..n]).await?;
+    }
+}